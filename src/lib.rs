@@ -15,12 +15,72 @@
 //! let chosen = r.pick_top(&"my-key");
 //! assert!(chosen.is_some());
 //! ```
+use std::borrow::Borrow;
 use std::collections::hash_map::RandomState;
-use std::hash::{BuildHasher, Hash, Hasher};
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+/// Hashes `node` alone; cached once per node so picks don't re-hash it.
+#[inline]
+fn node_digest<N: Hash, S: BuildHasher>(node: &N, build: &S) -> u64 {
+    build.hash_one(node)
+}
+
+/// Hashes `key` alone; done exactly once per pick.
+#[inline]
+fn key_hash<K: Hash, S: BuildHasher>(key: &K, build: &S) -> u64 {
+    build.hash_one(key)
+}
+
+/// Combines a key hash and a node digest into a per-(key, node) HRW score
+/// with a splitmix64-style avalanche, so the hot loop over nodes is a tight
+/// integer kernel instead of a full hasher run per node.
+#[inline]
+fn mix(kh: u64, nh: u64) -> u64 {
+    let mut x = kh ^ nh.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Builds the digest-to-positions membership index for a freshly assembled
+/// `digests` vector.
+fn build_index(digests: &[u64]) -> HashMap<u64, Vec<usize>> {
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, &digest) in digests.iter().enumerate() {
+        index.entry(digest).or_default().push(i);
+    }
+    index
+}
+
+/// Removes position `i` from the bucket at `digest`, dropping the bucket
+/// entirely once it's empty.
+fn remove_from_bucket(index: &mut HashMap<u64, Vec<usize>>, digest: u64, i: usize) {
+    if let Some(bucket) = index.get_mut(&digest) {
+        if let Some(pos) = bucket.iter().position(|&x| x == i) {
+            bucket.swap_remove(pos);
+        }
+        if bucket.is_empty() {
+            index.remove(&digest);
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Rendezvous<N, S = RandomState> {
     nodes: Vec<N>,
+    /// `weights[i]` is the capacity weight of `nodes[i]`, if one was set via
+    /// `add_weighted_node`. `None` means the default weight of `1.0`.
+    weights: Vec<Option<f64>>,
+    /// `digests[i]` is `nodes[i]` hashed alone, computed once when the node
+    /// is added so picks only need to hash the key.
+    digests: Vec<u64>,
+    /// Maps a node's cached digest to the positions in `nodes` of every node
+    /// sharing that digest (almost always a single element; more only on a
+    /// hash collision). This gives O(1) average membership checks and
+    /// removal without storing each node a second time: `nodes` remains the
+    /// one owning copy.
+    index: HashMap<u64, Vec<usize>>,
     build: S,
 }
 
@@ -32,14 +92,25 @@ where
     pub fn new() -> Self {
         Self {
             nodes: Vec::new(),
+            weights: Vec::new(),
+            digests: Vec::new(),
+            index: HashMap::new(),
             build: RandomState::new(),
         }
     }
 
     pub fn from_nodes(nodes: impl IntoIterator<Item = N>) -> Self {
+        let build = RandomState::new();
+        let nodes: Vec<N> = nodes.into_iter().collect();
+        let weights = vec![None; nodes.len()];
+        let digests: Vec<u64> = nodes.iter().map(|n| node_digest(n, &build)).collect();
+        let index = build_index(&digests);
         Self {
-            nodes: nodes.into_iter().collect(),
-            build: RandomState::new(),
+            nodes,
+            weights,
+            digests,
+            index,
+            build,
         }
     }
 }
@@ -60,69 +131,234 @@ where
 {
     /// Construct with a custom hasher builder (e.g., ahash::RandomState).
     pub fn from_nodes_and_hasher(nodes: impl IntoIterator<Item = N>, build: S) -> Self {
+        let nodes: Vec<N> = nodes.into_iter().collect();
+        let weights = vec![None; nodes.len()];
+        let digests: Vec<u64> = nodes.iter().map(|n| node_digest(n, &build)).collect();
+        let index = build_index(&digests);
         Self {
-            nodes: nodes.into_iter().collect(),
+            nodes,
+            weights,
+            digests,
+            index,
             build,
         }
     }
 
+    /// Finds the position of a node equal to `q` among those sharing
+    /// `digest`, resolving hash collisions by falling back to `Eq`.
+    fn position_of<Q>(&self, digest: u64, q: &Q) -> Option<usize>
+    where
+        N: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.index
+            .get(&digest)?
+            .iter()
+            .copied()
+            .find(|&i| self.nodes[i].borrow() == q)
+    }
+
     pub fn add_node(&mut self, node: N) -> bool {
-        if !self.nodes.iter().any(|n| n == &node) {
+        let digest = node_digest(&node, &self.build);
+        if self.position_of(digest, &node).is_some() {
+            return false;
+        }
+        let i = self.nodes.len();
+        self.digests.push(digest);
+        self.index.entry(digest).or_default().push(i);
+        self.nodes.push(node);
+        self.weights.push(None);
+        true
+    }
+
+    /// Adds `node` with a capacity weight, biasing picks proportionally: a
+    /// node with weight `2.0` receives roughly twice the key share of a node
+    /// with weight `1.0`. If `node` already exists, its weight is updated in
+    /// place and `false` is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is not a positive, finite number.
+    pub fn add_weighted_node(&mut self, node: N, weight: f64) -> bool {
+        assert!(
+            weight.is_finite() && weight > 0.0,
+            "node weight must be positive and finite, got {weight}"
+        );
+        let digest = node_digest(&node, &self.build);
+        if let Some(i) = self.position_of(digest, &node) {
+            self.weights[i] = Some(weight);
+            false
+        } else {
+            let i = self.nodes.len();
+            self.digests.push(digest);
+            self.index.entry(digest).or_default().push(i);
             self.nodes.push(node);
+            self.weights.push(Some(weight));
             true
-        } else {
-            false
         }
     }
 
-    pub fn remove_node(&mut self, node: &N) -> bool {
-        if let Some(i) = self.nodes.iter().position(|n| n == node) {
-            self.nodes.swap_remove(i);
-            true
-        } else {
-            false
+    /// Removes a node looked up by any borrowed form `&Q` of `N` (e.g. `&str`
+    /// for `Rendezvous<String>`), so callers don't need to allocate an owned
+    /// `N` just to remove one.
+    pub fn remove_node<Q>(&mut self, node: &Q) -> bool
+    where
+        N: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let digest = self.build.hash_one(node);
+        let Some(i) = self.position_of(digest, node) else {
+            return false;
+        };
+        remove_from_bucket(&mut self.index, digest, i);
+
+        let last = self.nodes.len() - 1;
+        self.nodes.swap_remove(i);
+        self.weights.swap_remove(i);
+        self.digests.swap_remove(i);
+        if i != last {
+            // The node that used to sit at `last` now lives at `i`; its
+            // digest bucket still points at `last` and needs updating.
+            let moved_digest = self.digests[i];
+            if let Some(slot) = self
+                .index
+                .get_mut(&moved_digest)
+                .and_then(|bucket| bucket.iter_mut().find(|slot| **slot == last))
+            {
+                *slot = i;
+            }
         }
+        true
+    }
+
+    /// Returns `true` if `node` is currently in the set (O(1) average),
+    /// looked up by any borrowed form `&Q` of `N`.
+    pub fn contains<Q>(&self, node: &Q) -> bool
+    where
+        N: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let digest = self.build.hash_one(node);
+        self.position_of(digest, node).is_some()
+    }
+
+    /// Transforms a raw HRW score into the weighted score from Resch's
+    /// weighted-rendezvous-hashing scheme: the raw score is normalized to a
+    /// float `h` in the open interval `(0, 1)`, then combined with `weight`
+    /// as `-weight / ln(h)`, which is always positive since `ln(h) < 0`.
+    /// Larger weights and larger raw scores both push the result up, so
+    /// ranking by descending weighted score reproduces plain HRW ranking
+    /// when every node shares the same weight.
+    ///
+    /// `h` is derived from the high 53 bits of `raw` (the same bit width
+    /// `rand` uses for its `[0, 1)` float conversion) with the lowest of
+    /// those bits set to `1`, divided by `2^53`, rather than
+    /// `(raw + 1) / 2^64`. That value fits exactly in an `f64` mantissa and
+    /// `2^53` is an exact power of two, so the division introduces no
+    /// rounding: `h` always lies in `[2^-53, 1 - 2^-53]`, strictly inside
+    /// `(0, 1)` even at `raw == 0` and `raw == u64::MAX`. The naive
+    /// `(raw + 1) / 2^64` form rounds to exactly `1.0` at `u64::MAX`,
+    /// making `ln(h)` exactly `0.0` instead of negative and flipping the
+    /// sign of the result.
+    #[inline]
+    fn weighted_score(raw: u64, weight: f64) -> f64 {
+        let h = ((raw >> 11) | 1) as f64 / (1u64 << 53) as f64;
+        -weight / h.ln()
     }
 
     #[inline]
-    fn hrw_score<K: Hash>(key: &K, node: &N, build: &S) -> u64 {
-        let mut h = build.build_hasher();
-        key.hash(&mut h);
-        node.hash(&mut h);
-        h.finish()
+    fn has_weights(&self) -> bool {
+        self.weights.iter().any(Option::is_some)
     }
 
-    /// Pick the single best node (O(N) max scan).
+    /// Pick the single best node. The key is hashed once; each node then
+    /// only costs a cheap integer mix against its cached digest (O(N)).
     pub fn pick_top<K: Hash>(&self, key: &K) -> Option<&N> {
-        self.nodes
-            .iter()
-            .max_by_key(|n| Self::hrw_score(key, *n, &self.build))
+        let kh = key_hash(key, &self.build);
+        if self.has_weights() {
+            (0..self.nodes.len())
+                .max_by(|&i, &j| {
+                    let si = Self::weighted_score(mix(kh, self.digests[i]), self.weights[i].unwrap_or(1.0));
+                    let sj = Self::weighted_score(mix(kh, self.digests[j]), self.weights[j].unwrap_or(1.0));
+                    si.total_cmp(&sj)
+                })
+                .map(|i| &self.nodes[i])
+        } else {
+            (0..self.nodes.len())
+                .max_by_key(|&i| mix(kh, self.digests[i]))
+                .map(|i| &self.nodes[i])
+        }
     }
 
-    /// Pick the top-k nodes with partial selection (O(N) + O(k log k))
+    /// Pick the top-k nodes with partial selection (O(N) + O(k log k)). The
+    /// key is hashed once and mixed with each node's cached digest.
     pub fn pick_top_k<K: Hash>(&self, key: &K, k: usize) -> Vec<&N> {
         if self.nodes.is_empty() || k == 0 {
             return Vec::new();
         }
         let k = k.min(self.nodes.len());
+        let kh = key_hash(key, &self.build);
 
-        let mut scored: Vec<_> = self
-            .nodes
-            .iter()
-            .map(|n| (Self::hrw_score(key, n, &self.build), n))
-            .collect();
+        if self.has_weights() {
+            let mut scored: Vec<(f64, usize)> = (0..self.nodes.len())
+                .map(|i| {
+                    let s = Self::weighted_score(mix(kh, self.digests[i]), self.weights[i].unwrap_or(1.0));
+                    (s, i)
+                })
+                .collect();
 
-        let k = k.min(scored.len());
-        let nth = k - 1;
+            let nth = k - 1;
+            scored.select_nth_unstable_by(nth, |a, b| b.0.total_cmp(&a.0));
+            scored[..k].sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+            scored[..k].iter().map(|&(_, i)| &self.nodes[i]).collect()
+        } else {
+            let mut scored: Vec<(u64, usize)> = (0..self.nodes.len())
+                .map(|i| (mix(kh, self.digests[i]), i))
+                .collect();
 
-        // After this, the top-k elements are in scored[..k] in arbitrary order
-        scored.select_nth_unstable_by(nth, |a, b| b.0.cmp(&a.0));
+            // After this, the top-k elements are in scored[..k] in arbitrary order
+            let nth = k - 1;
+            scored.select_nth_unstable_by(nth, |a, b| b.0.cmp(&a.0));
 
-        // Now sort only the top-k slice to get deterministic replica order
-        scored[..k].sort_unstable_by(|a, b| b.0.cmp(&a.0));
+            // Now sort only the top-k slice to get deterministic replica order
+            scored[..k].sort_unstable_by(|a, b| b.0.cmp(&a.0));
 
-        // Return the top-k nodes
-        scored[..k].iter().map(|&(_, n)| n).collect()
+            // Return the top-k nodes
+            scored[..k].iter().map(|&(_, i)| &self.nodes[i]).collect()
+        }
+    }
+
+    /// Ranks every node by descending HRW score and returns them as a lazy
+    /// iterator, for callers that want to walk the full ranking (e.g. replica
+    /// placement with failover) without committing to a `k` up front.
+    ///
+    /// Building the iterator costs O(N) (heapifying the node set), and each
+    /// subsequent `next()` call costs O(log N). The ordering agrees with
+    /// `pick_top_k` for every prefix length: both rank by the same score,
+    /// weighted or not.
+    pub fn calc_candidates<'a, K: Hash>(&'a self, key: &K) -> Candidates<'a, N> {
+        let kh = key_hash(key, &self.build);
+        if self.has_weights() {
+            let heap = (0..self.nodes.len())
+                .map(|i| WeightedCandidate {
+                    score: Self::weighted_score(mix(kh, self.digests[i]), self.weights[i].unwrap_or(1.0)),
+                    node: &self.nodes[i],
+                })
+                .collect();
+            Candidates {
+                heap: CandidateHeap::Weighted(heap),
+            }
+        } else {
+            let heap = (0..self.nodes.len())
+                .map(|i| RawCandidate {
+                    score: mix(kh, self.digests[i]),
+                    node: &self.nodes[i],
+                })
+                .collect();
+            Candidates {
+                heap: CandidateHeap::Raw(heap),
+            }
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -134,6 +370,86 @@ where
     }
 }
 
+struct RawCandidate<'a, N> {
+    score: u64,
+    node: &'a N,
+}
+
+impl<N> PartialEq for RawCandidate<'_, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+
+impl<N> Eq for RawCandidate<'_, N> {}
+
+impl<N> PartialOrd for RawCandidate<'_, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for RawCandidate<'_, N> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score.cmp(&other.score)
+    }
+}
+
+struct WeightedCandidate<'a, N> {
+    score: f64,
+    node: &'a N,
+}
+
+impl<N> PartialEq for WeightedCandidate<'_, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+
+impl<N> Eq for WeightedCandidate<'_, N> {}
+
+impl<N> PartialOrd for WeightedCandidate<'_, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for WeightedCandidate<'_, N> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score.total_cmp(&other.score)
+    }
+}
+
+enum CandidateHeap<'a, N> {
+    Raw(BinaryHeap<RawCandidate<'a, N>>),
+    Weighted(BinaryHeap<WeightedCandidate<'a, N>>),
+}
+
+/// Lazy, descending-score iterator over the full node set, produced by
+/// [`Rendezvous::calc_candidates`].
+pub struct Candidates<'a, N> {
+    heap: CandidateHeap<'a, N>,
+}
+
+impl<'a, N> Iterator for Candidates<'a, N> {
+    type Item = &'a N;
+
+    fn next(&mut self) -> Option<&'a N> {
+        match &mut self.heap {
+            CandidateHeap::Raw(heap) => heap.pop().map(|c| c.node),
+            CandidateHeap::Weighted(heap) => heap.pop().map(|c| c.node),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match &self.heap {
+            CandidateHeap::Raw(heap) => heap.len(),
+            CandidateHeap::Weighted(heap) => heap.len(),
+        };
+        (remaining, Some(remaining))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -263,4 +579,156 @@ mod tests {
         }
         // Should not panic if k > nodes.len()
     }
+
+    #[test]
+    fn weighted_node_shifts_key_share() {
+        use std::collections::HashMap;
+
+        let mut r = Rendezvous::from_nodes(["a", "b"]);
+        r.add_weighted_node("heavy", 20.0);
+
+        let mut counts = HashMap::new();
+        for i in 0..1000 {
+            *counts.entry(*r.pick_top(&i).unwrap()).or_insert(0) += 1;
+        }
+
+        // With 20x the weight of each unweighted peer, "heavy" should win
+        // the large majority of keys.
+        assert!(counts.get("heavy").copied().unwrap_or(0) > 700, "{counts:?}");
+    }
+
+    #[test]
+    fn weighted_score_stays_positive_at_u64_max() {
+        let score = Rendezvous::<&str>::weighted_score(u64::MAX, 1.0);
+        assert!(score.is_finite() && score > 0.0, "{score}");
+    }
+
+    #[test]
+    fn add_weighted_node_updates_existing_weight() {
+        let mut r = Rendezvous::from_nodes(["a"]);
+        assert!(!r.add_weighted_node("a", 2.0));
+        assert_eq!(r.len(), 1);
+        assert_eq!(r.weights[0], Some(2.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "positive and finite")]
+    fn add_weighted_node_rejects_non_positive_weight() {
+        let mut r = Rendezvous::<&str>::new();
+        r.add_weighted_node("a", 0.0);
+    }
+
+    #[test]
+    fn calc_candidates_matches_pick_top_k_for_every_prefix() {
+        let r = Rendezvous::from_nodes(["A", "B", "C", "D", "E"]);
+        let key = "some-key";
+        let ranked: Vec<&str> = r.calc_candidates(&key).copied().collect();
+        assert_eq!(ranked.len(), r.len());
+        for k in 1..=r.len() {
+            assert_eq!(r.pick_top_k(&key, k), ranked[..k].iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn calc_candidates_matches_pick_top_k_when_weighted() {
+        let mut r = Rendezvous::from_nodes(["A", "B", "C"]);
+        r.add_weighted_node("D", 5.0);
+        let key = "another-key";
+        let ranked: Vec<&str> = r.calc_candidates(&key).copied().collect();
+        for k in 1..=r.len() {
+            assert_eq!(r.pick_top_k(&key, k), ranked[..k].iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn picks_stay_well_distributed_with_cached_digests() {
+        use std::collections::HashMap;
+
+        let r = Rendezvous::from_nodes(["a", "b", "c", "d"]);
+        let mut counts = HashMap::new();
+        for i in 0..4000 {
+            *counts.entry(*r.pick_top(&i).unwrap()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        for count in counts.values() {
+            assert!((800..=1200).contains(count), "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn node_churn_keeps_storage_consistent() {
+        let mut r = Rendezvous::from_nodes(["A", "B", "C", "D", "E"]);
+        // Remove from the middle, then from the front; each removal moves
+        // the last node into the freed slot, exercising the swap-remove
+        // reindexing.
+        assert!(r.remove_node(&"B"));
+        assert!(r.remove_node(&"A"));
+        assert!(r.add_node("F"));
+        assert_eq!(r.len(), 4);
+
+        for node in ["C", "D", "E", "F"] {
+            assert!(r.remove_node(&node), "{node} should still be present");
+        }
+        assert!(r.is_empty());
+        assert!(!r.remove_node(&"C"));
+    }
+
+    #[test]
+    fn borrowed_key_lookups_on_string_nodes() {
+        let mut r: Rendezvous<String> =
+            Rendezvous::from_nodes(["alpha".to_string(), "beta".to_string()]);
+
+        // No owned String needed to check membership or remove by &str.
+        assert!(r.contains("alpha"));
+        assert!(!r.contains("gamma"));
+        assert!(r.remove_node("alpha"));
+        assert!(!r.contains("alpha"));
+        assert_eq!(r.len(), 1);
+    }
+
+    /// A `BuildHasher` that hashes every value to the same digest, used to
+    /// force multiple nodes into one index bucket and exercise collision
+    /// handling.
+    #[derive(Clone)]
+    struct ConstantHasher;
+
+    /// A `Hasher` that ignores its input and always finishes with `0`.
+    struct ZeroHasher;
+
+    impl std::hash::Hasher for ZeroHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    impl BuildHasher for ConstantHasher {
+        type Hasher = ZeroHasher;
+
+        fn build_hasher(&self) -> Self::Hasher {
+            // The hasher discards every write, so every node and key
+            // collapses onto the same digest regardless of its value.
+            ZeroHasher
+        }
+    }
+
+    #[test]
+    fn membership_and_removal_survive_digest_collisions() {
+        let mut r =
+            Rendezvous::from_nodes_and_hasher(["A", "B", "C"], ConstantHasher);
+        assert!(r.contains(&"A"));
+        assert!(r.contains(&"B"));
+        assert!(r.contains(&"C"));
+
+        assert!(r.remove_node(&"B"));
+        assert!(!r.contains(&"B"));
+        assert!(r.contains(&"A"));
+        assert!(r.contains(&"C"));
+        assert_eq!(r.len(), 2);
+
+        assert!(r.remove_node(&"A"));
+        assert!(r.remove_node(&"C"));
+        assert!(r.is_empty());
+    }
 }